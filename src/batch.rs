@@ -0,0 +1,106 @@
+//! Batch encode/decode over slices of points.
+//!
+//! Building a Morton-ordered index over a whole point cloud by calling
+//! [`crate::morton_encode`] in a loop pays for a bounds check and a
+//! `Platform::detect()` branch on every element. These functions instead
+//! process 4 points per call on an AVX2 machine (see [`crate::avx2`]) and
+//! fall back to the scalar loop for the remainder and on machines without
+//! AVX2.
+
+/// Morton-encode `xs[i]`/`ys[i]` pairs into `out[i]`.
+///
+/// # Panics
+///
+/// Panics if `xs`, `ys`, and `out` don't all have the same length.
+pub fn morton_encode_slice(xs: &[u32], ys: &[u32], out: &mut [u64]) {
+    assert_eq!(xs.len(), ys.len());
+    assert_eq!(xs.len(), out.len());
+
+    let mut i = 0;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        while i + 4 <= xs.len() {
+            let xs4: [u32; 4] = xs[i..i + 4].try_into().unwrap();
+            let ys4: [u32; 4] = ys[i..i + 4].try_into().unwrap();
+            let mut out4 = [0u64; 4];
+            unsafe { crate::avx2::morton_encode_4(&xs4, &ys4, &mut out4) };
+            out[i..i + 4].copy_from_slice(&out4);
+            i += 4;
+        }
+    }
+
+    for j in i..xs.len() {
+        out[j] = crate::morton_encode(xs[j], ys[j]);
+    }
+}
+
+/// Morton-decode `keys[i]` into `out_xs[i]`/`out_ys[i]`.
+///
+/// # Panics
+///
+/// Panics if `keys`, `out_xs`, and `out_ys` don't all have the same length.
+pub fn morton_decode_slice(keys: &[u64], out_xs: &mut [u32], out_ys: &mut [u32]) {
+    assert_eq!(keys.len(), out_xs.len());
+    assert_eq!(keys.len(), out_ys.len());
+
+    let mut i = 0;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        while i + 4 <= keys.len() {
+            let keys4: [u64; 4] = keys[i..i + 4].try_into().unwrap();
+            let mut xs4 = [0u32; 4];
+            let mut ys4 = [0u32; 4];
+            unsafe { crate::avx2::morton_decode_4(&keys4, &mut xs4, &mut ys4) };
+            out_xs[i..i + 4].copy_from_slice(&xs4);
+            out_ys[i..i + 4].copy_from_slice(&ys4);
+            i += 4;
+        }
+    }
+
+    for j in i..keys.len() {
+        let (x, y) = crate::morton_decode(keys[j]);
+        out_xs[j] = x;
+        out_ys[j] = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_morton_encode_slice() {
+        let xs = [0x123456u32, 0x1, 0xffffffff, 0, 7, 8, 9, 10, 11];
+        let ys = [0x456789u32, 0x2, 0xffffffff, 0, 7, 8, 9, 10, 11];
+        let mut out = [0u64; 9];
+        morton_encode_slice(&xs, &ys, &mut out);
+        for i in 0..xs.len() {
+            assert_eq!(crate::morton_encode(xs[i], ys[i]), out[i]);
+        }
+    }
+
+    #[test]
+    fn test_morton_decode_slice() {
+        let xs = [0x123456u32, 0x1, 0xffffffff, 0, 7, 8, 9, 10, 11];
+        let ys = [0x456789u32, 0x2, 0xffffffff, 0, 7, 8, 9, 10, 11];
+        let mut keys = [0u64; 9];
+        morton_encode_slice(&xs, &ys, &mut keys);
+
+        let mut out_xs = [0u32; 9];
+        let mut out_ys = [0u32; 9];
+        morton_decode_slice(&keys, &mut out_xs, &mut out_ys);
+        assert_eq!(xs, out_xs);
+        assert_eq!(ys, out_ys);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_morton_encode_slice_length_mismatch() {
+        let xs = [0u32; 3];
+        let ys = [0u32; 3];
+        let mut out = [0u64; 2];
+        morton_encode_slice(&xs, &ys, &mut out);
+    }
+}
@@ -0,0 +1,203 @@
+//! BMI2 (`pdep`/`pext`) accelerated morton encode/decode.
+//!
+//! Uses the stable `core::arch` intrinsics instead of the nightly-only
+//! `link_llvm_intrinsics` FFI, so this path is reachable from a regular
+//! stable build; callers must check `is_x86_feature_detected!("bmi2")`
+//! (or go through [`crate::platform::Platform`]) before calling these
+//! functions.
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::{_pdep_u32, _pdep_u64, _pext_u32, _pext_u64};
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::{_pdep_u32, _pdep_u64, _pext_u32, _pext_u64};
+
+const PATTERN: u64 = 0x5555555555555555;
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_encode(x: u32, y: u32) -> u64 {
+    (_pdep_u64(y as u64, PATTERN) << 1) | _pdep_u64(x as u64, PATTERN)
+}
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_decode(a: u64) -> (u32, u32) {
+    (
+        _pext_u64(a, PATTERN) as u32,
+        _pext_u64(a >> 1, PATTERN) as u32,
+    )
+}
+
+const PATTERN_3D: u64 = 0x1249249249249249;
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_encode_3d(x: u32, y: u32, z: u32) -> u64 {
+    _pdep_u64(x as u64, PATTERN_3D)
+        | (_pdep_u64(y as u64, PATTERN_3D) << 1)
+        | (_pdep_u64(z as u64, PATTERN_3D) << 2)
+}
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_decode_3d(a: u64) -> (u32, u32, u32) {
+    (
+        _pext_u64(a, PATTERN_3D) as u32,
+        _pext_u64(a >> 1, PATTERN_3D) as u32,
+        _pext_u64(a >> 2, PATTERN_3D) as u32,
+    )
+}
+
+const PATTERN_U32: u32 = 0x55555555;
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_encode_u16(x: u16, y: u16) -> u32 {
+    (_pdep_u32(y as u32, PATTERN_U32) << 1) | _pdep_u32(x as u32, PATTERN_U32)
+}
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_decode_u16(a: u32) -> (u16, u16) {
+    (
+        _pext_u32(a, PATTERN_U32) as u16,
+        _pext_u32(a >> 1, PATTERN_U32) as u16,
+    )
+}
+
+// u64 -> u128: BMI2 has no 128-bit `pdep`/`pext`, so each coordinate is
+// split into 32-bit halves, each spread with `_pdep_u64`, and the two
+// 64-bit results are stacked into the 128-bit key (see `portable::spread_u64`
+// for why this is equivalent to a single wider interleave).
+#[target_feature(enable = "bmi2")]
+unsafe fn spread_u64(x: u64) -> u128 {
+    let lo = _pdep_u64(x as u32 as u64, PATTERN) as u128;
+    let hi = _pdep_u64((x >> 32) as u32 as u64, PATTERN) as u128;
+    lo | (hi << 64)
+}
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_encode_u64(x: u64, y: u64) -> u128 {
+    (spread_u64(y) << 1) | spread_u64(x)
+}
+
+#[target_feature(enable = "bmi2")]
+unsafe fn compact_u128(a: u128, shift: u32) -> u64 {
+    let lo = _pext_u64(a as u64 >> shift, PATTERN);
+    let hi = _pext_u64((a >> 64) as u64 >> shift, PATTERN);
+    lo | (hi << 32)
+}
+
+/// # Safety
+///
+/// Caller must ensure the BMI2 instruction set extension is available
+/// (e.g. via `is_x86_feature_detected!("bmi2")`).
+#[target_feature(enable = "bmi2")]
+pub unsafe fn morton_decode_u64(a: u128) -> (u64, u64) {
+    (compact_u128(a, 0), compact_u128(a, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        INPUT, INPUT_3D, INPUT_U16, INPUT_U64, OUTPUT, OUTPUT_3D, OUTPUT_U16, OUTPUT_U64,
+    };
+
+    #[test]
+    fn test_morton_encode() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let (x, y) = INPUT;
+        let encoded = unsafe { super::morton_encode(x, y) };
+        assert_eq!(OUTPUT, encoded);
+    }
+
+    #[test]
+    fn test_morton_decode() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let decoded = unsafe { super::morton_decode(OUTPUT) };
+        assert_eq!(INPUT, decoded);
+    }
+
+    #[test]
+    fn test_morton_encode_3d() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let (x, y, z) = INPUT_3D;
+        let encoded = unsafe { super::morton_encode_3d(x, y, z) };
+        assert_eq!(OUTPUT_3D, encoded);
+    }
+
+    #[test]
+    fn test_morton_decode_3d() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let decoded = unsafe { super::morton_decode_3d(OUTPUT_3D) };
+        assert_eq!(INPUT_3D, decoded);
+    }
+
+    #[test]
+    fn test_morton_encode_u16() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let (x, y) = INPUT_U16;
+        let encoded = unsafe { super::morton_encode_u16(x, y) };
+        assert_eq!(OUTPUT_U16, encoded);
+    }
+
+    #[test]
+    fn test_morton_decode_u16() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let decoded = unsafe { super::morton_decode_u16(OUTPUT_U16) };
+        assert_eq!(INPUT_U16, decoded);
+    }
+
+    #[test]
+    fn test_morton_encode_u64() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let (x, y) = INPUT_U64;
+        let encoded = unsafe { super::morton_encode_u64(x, y) };
+        assert_eq!(OUTPUT_U64, encoded);
+    }
+
+    #[test]
+    fn test_morton_decode_u64() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let decoded = unsafe { super::morton_decode_u64(OUTPUT_U64) };
+        assert_eq!(INPUT_U64, decoded);
+    }
+}
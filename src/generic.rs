@@ -0,0 +1,87 @@
+//! Morton encode/decode generic over coordinate width.
+//!
+//! The fixed-width [`crate::morton_encode`]/[`crate::morton_decode`] (and
+//! their `_3d` siblings) only cover `u32` coordinates packed into a `u64`
+//! key. Not every caller needs that much precision: a low-resolution grid
+//! is cheaper and more cache-friendly with a `u16`-into-`u32` key, while
+//! high-resolution geospatial work wants a `u64`-into-`u128` key. The
+//! [`MortonKey`] trait picks the width at the call site, still dispatching
+//! to BMI2 where it is available.
+
+use crate::platform::Platform;
+
+/// A coordinate type that can be interleaved into a Morton key twice as
+/// wide as itself.
+pub trait MortonKey: Sized {
+    /// The interleaved key type.
+    type Key;
+
+    fn morton_encode(x: Self, y: Self) -> Self::Key;
+    fn morton_decode(key: Self::Key) -> (Self, Self);
+}
+
+impl MortonKey for u16 {
+    type Key = u32;
+
+    fn morton_encode(x: u16, y: u16) -> u32 {
+        Platform::detect().morton_encode_u16(x, y)
+    }
+
+    fn morton_decode(key: u32) -> (u16, u16) {
+        Platform::detect().morton_decode_u16(key)
+    }
+}
+
+impl MortonKey for u32 {
+    type Key = u64;
+
+    fn morton_encode(x: u32, y: u32) -> u64 {
+        Platform::detect().morton_encode(x, y)
+    }
+
+    fn morton_decode(key: u64) -> (u32, u32) {
+        Platform::detect().morton_decode(key)
+    }
+}
+
+impl MortonKey for u64 {
+    type Key = u128;
+
+    fn morton_encode(x: u64, y: u64) -> u128 {
+        Platform::detect().morton_encode_u64(x, y)
+    }
+
+    fn morton_decode(key: u128) -> (u64, u64) {
+        Platform::detect().morton_decode_u64(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MortonKey;
+    use crate::{INPUT, INPUT_U16, INPUT_U64, OUTPUT, OUTPUT_U16, OUTPUT_U64};
+
+    #[test]
+    fn test_u16_roundtrip() {
+        let (x, y) = INPUT_U16;
+        let encoded = u16::morton_encode(x, y);
+        assert_eq!(OUTPUT_U16, encoded);
+        assert_eq!((x, y), u16::morton_decode(encoded));
+    }
+
+    #[test]
+    fn test_u32_matches_fixed_width_api() {
+        let (x, y) = INPUT;
+        let encoded = u32::morton_encode(x, y);
+        assert_eq!(OUTPUT, encoded);
+        assert_eq!((x, y), u32::morton_decode(encoded));
+    }
+
+    #[test]
+    fn test_u64_roundtrip() {
+        let (x, y) = INPUT_U64;
+        let encoded = u64::morton_encode(x, y);
+        assert_eq!(OUTPUT_U64, encoded);
+        assert_eq!((x, y), u64::morton_decode(encoded));
+    }
+}
@@ -1,96 +1,63 @@
 //! Morton encoding functions.
 //!
-//! Includes a Intel BMI2 version for ~10x speed.
-//! Use `RUSTFLAGS="-C target-cpu=native"` when building to possibly
-//! get the machine-dependent version
+//! Dispatches through a runtime [`Platform`](platform::Platform) check, so
+//! a single stable build gets the Intel BMI2 fast path (~10x speed) on
+//! capable CPUs and falls back to the `portable` implementation everywhere
+//! else -- no nightly or `RUSTFLAGS="-C target-cpu=native"` required.
 
 #![cfg_attr(all(feature = "nightly", test), feature(test))]
-#![cfg_attr(feature = "nightly", feature(cfg_target_feature))]
-#![cfg_attr(feature = "nightly", feature(link_llvm_intrinsics))]
 
-#[cfg(not(all(feature = "nightly", target_feature = "bmi2")))]
-pub use portable::{morton_encode, morton_decode};
-#[cfg(all(feature = "nightly", target_feature = "bmi2"))]
-pub use bmi::{morton_encode, morton_decode};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub mod avx2;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub mod bmi2;
+pub mod batch;
+pub mod generic;
+pub mod platform;
+pub mod range;
+
+pub use batch::{morton_decode_slice, morton_encode_slice};
+pub use generic::MortonKey;
+pub use range::{morton_decrement_dim, morton_increment_dim, zorder_next_in_range};
+use platform::Platform;
 
 #[cfg(test)]
 const INPUT: (u32, u32) = (0x123456, 0x456789);
 #[cfg(test)]
 const OUTPUT: u64 = 0x21262d3a9196;
 
-#[cfg(all(feature = "nightly", target_feature = "bmi2"))]
-pub mod bmi {
-
-    mod x86 {
-        extern "C" {
-            #[link_name = "llvm.x86.bmi.pdep.64"]
-            pub fn bmi_pdep_64(a: i64, b: i64) -> i64;
-
-            #[link_name = "llvm.x86.bmi.pext.64"]
-            pub fn bmi_pext_64(a: i64, b: i64) -> i64;
-        }
-    }
-
-    const PATTERN: i64 = 0x5555555555555555;
-
-    pub fn morton_encode(x: u32, y: u32) -> u64 {
-        unsafe {
-            ((x86::bmi_pdep_64(y as i64, PATTERN) << 1) |
-             x86::bmi_pdep_64(x as i64, PATTERN)) as u64
-        }
-    }
-
-    pub fn morton_decode(a: u64) -> (u32, u32) {
-        unsafe {
-            (x86::bmi_pext_64(a as i64, PATTERN) as u32,
-             x86::bmi_pext_64(a as i64 >> 1, PATTERN) as u32)
-        }
-    }
-
-    #[cfg(test)]
-    mod tests {
-        use super::super::{INPUT, OUTPUT};
+#[cfg(test)]
+const INPUT_3D: (u32, u32, u32) = (0x12345, 0x6789a, 0x3456);
+#[cfg(test)]
+const OUTPUT_3D: u64 = 0x910be509546571;
 
-        #[test]
-        fn test_morton_encode() {
-            let (x, y) = INPUT;
-            let encoded = super::morton_encode(x, y);
-            println!("bmi::morton_encode({}, {}) -> {}", x, y, encoded);
-            assert_eq!(OUTPUT, encoded);
-        }
+#[cfg(test)]
+const INPUT_U16: (u16, u16) = (0x1234, 0x5678);
+#[cfg(test)]
+const OUTPUT_U16: u32 = 0x232c2f90;
 
-        #[test]
-        fn test_morton_decode() {
-            let decoded = super::morton_decode(OUTPUT);
-            println!("bmi::morton_decode({}) -> {:?}", OUTPUT, decoded);
+#[cfg(test)]
+const INPUT_U64: (u64, u64) = (0x123456789abcdef0, 0xfedcba9876543210);
+#[cfg(test)]
+const OUTPUT_U64: u128 = 0xabaca7b09b9c97c06b6c67705b5c5700;
 
-            assert_eq!(INPUT, decoded);
-        }
+pub fn morton_encode(x: u32, y: u32) -> u64 {
+    Platform::detect().morton_encode(x, y)
+}
 
-        extern crate test;
+pub fn morton_decode(a: u64) -> (u32, u32) {
+    Platform::detect().morton_decode(a)
+}
 
-        #[bench]
-        fn bench_1k_morton_decode(b: &mut test::Bencher) {
-            let x = test::black_box(0x5555555555555555);
-            b.iter(|| {
-                for _ in 0..1_000 {
-                    let coords = super::morton_decode(x);
-                    test::black_box(coords);
-                }
-            });
-        }
+/// Interleaves three 21-bit coordinates into a 63-bit Morton (Z-order) key.
+///
+/// Each of `x`, `y`, `z` is limited to 21 bits; higher bits are discarded.
+pub fn morton_encode_3d(x: u32, y: u32, z: u32) -> u64 {
+    Platform::detect().morton_encode_3d(x, y, z)
+}
 
-        #[bench]
-        fn bench_1k_morton_encode(b: &mut test::Bencher) {
-            let (x, y) = test::black_box(INPUT);
-            b.iter(|| {
-                for _ in 0..1_000 {
-                    let encoded = super::morton_encode(x, y);
-                    test::black_box(encoded);
-                }
-            });
-        }
-    }
+pub fn morton_decode_3d(a: u64) -> (u32, u32, u32) {
+    Platform::detect().morton_decode_3d(a)
 }
 
 pub mod portable {
@@ -125,9 +92,94 @@ pub mod portable {
         (compact1by1(x), compact1by1(x >> 1))
     }
 
+    fn part1by2(x: u32) -> u64 {
+        let mut x = x as u64;
+        x &= 0x1fffff;
+        x = (x | (x << 32)) & 0x1f00000000ffff;
+        x = (x | (x << 16)) & 0x1f0000ff0000ff;
+        x = (x | (x << 8)) & 0x100f00f00f00f00f;
+        x = (x | (x << 4)) & 0x10c30c30c30c30c3;
+        x = (x | (x << 2)) & 0x1249249249249249;
+        x
+    }
+
+    pub fn morton_encode_3d(x: u32, y: u32, z: u32) -> u64 {
+        part1by2(x) | (part1by2(y) << 1) | (part1by2(z) << 2)
+    }
+
+    fn compact1by2(mut x: u64) -> u32 {
+        x &= 0x1249249249249249;
+        x = (x ^ (x >> 2)) & 0x10c30c30c30c30c3;
+        x = (x ^ (x >> 4)) & 0x100f00f00f00f00f;
+        x = (x ^ (x >> 8)) & 0x1f0000ff0000ff;
+        x = (x ^ (x >> 16)) & 0x1f00000000ffff;
+        x = (x ^ (x >> 32)) & 0x1fffff;
+        x as u32
+    }
+
+    pub fn morton_decode_3d(x: u64) -> (u32, u32, u32) {
+        (compact1by2(x), compact1by2(x >> 1), compact1by2(x >> 2))
+    }
+
+    // u16 -> u32 interleave: the same recurrence as `part1by1`/`compact1by1`,
+    // truncated to the steps needed for a 16-bit input.
+    fn part1by1_16(x: u16) -> u32 {
+        let mut x = x as u32;
+        x = (x ^ (x << 8)) & 0x00ff00ff;
+        x = (x ^ (x << 4)) & 0x0f0f0f0f;
+        x = (x ^ (x << 2)) & 0x33333333;
+        x = (x ^ (x << 1)) & 0x55555555;
+        x
+    }
+
+    pub fn morton_encode_u16(x: u16, y: u16) -> u32 {
+        (part1by1_16(y) << 1) + part1by1_16(x)
+    }
+
+    fn compact1by1_16(mut x: u32) -> u16 {
+        x &= 0x55555555;
+        x = (x ^ (x >> 1)) & 0x33333333;
+        x = (x ^ (x >> 2)) & 0x0f0f0f0f;
+        x = (x ^ (x >> 4)) & 0x00ff00ff;
+        x = (x ^ (x >> 8)) & 0x0000ffff;
+        x as u16
+    }
+
+    pub fn morton_decode_u16(x: u32) -> (u16, u16) {
+        (compact1by1_16(x), compact1by1_16(x >> 1))
+    }
+
+    // u64 -> u128 interleave: there is no native 128-bit width to spread
+    // bits across in one pass, so each 64-bit coordinate is split into its
+    // low/high 32-bit halves, each interleaved with the existing 32-bit
+    // chain, and the two 64-bit results are stacked into the 128-bit key.
+    fn spread_u64(x: u64) -> u128 {
+        let lo = part1by1(x as u32) as u128;
+        let hi = part1by1((x >> 32) as u32) as u128;
+        lo | (hi << 64)
+    }
+
+    pub fn morton_encode_u64(x: u64, y: u64) -> u128 {
+        (spread_u64(y) << 1) + spread_u64(x)
+    }
+
+    fn compact_u128(a: u128, shift: u32) -> u64 {
+        let lo = a as u64;
+        let hi = (a >> 64) as u64;
+        let lo = compact1by1(lo >> shift) as u64;
+        let hi = compact1by1(hi >> shift) as u64;
+        lo | (hi << 32)
+    }
+
+    pub fn morton_decode_u64(a: u128) -> (u64, u64) {
+        (compact_u128(a, 0), compact_u128(a, 1))
+    }
+
     #[cfg(test)]
     mod tests {
-        use super::super::{INPUT, OUTPUT};
+        use super::super::{
+            INPUT, INPUT_3D, INPUT_U16, INPUT_U64, OUTPUT, OUTPUT_3D, OUTPUT_U16, OUTPUT_U64,
+        };
 
         #[test]
         fn test_morton_encode() {
@@ -145,6 +197,45 @@ pub mod portable {
             assert_eq!(INPUT, decoded);
         }
 
+        #[test]
+        fn test_morton_encode_3d() {
+            let (x, y, z) = INPUT_3D;
+            let encoded = super::morton_encode_3d(x, y, z);
+            assert_eq!(OUTPUT_3D, encoded);
+        }
+
+        #[test]
+        fn test_morton_decode_3d() {
+            let decoded = super::morton_decode_3d(OUTPUT_3D);
+            assert_eq!(INPUT_3D, decoded);
+        }
+
+        #[test]
+        fn test_morton_encode_u16() {
+            let (x, y) = INPUT_U16;
+            let encoded = super::morton_encode_u16(x, y);
+            assert_eq!(OUTPUT_U16, encoded);
+        }
+
+        #[test]
+        fn test_morton_decode_u16() {
+            let decoded = super::morton_decode_u16(OUTPUT_U16);
+            assert_eq!(INPUT_U16, decoded);
+        }
+
+        #[test]
+        fn test_morton_encode_u64() {
+            let (x, y) = INPUT_U64;
+            let encoded = super::morton_encode_u64(x, y);
+            assert_eq!(OUTPUT_U64, encoded);
+        }
+
+        #[test]
+        fn test_morton_decode_u64() {
+            let decoded = super::morton_decode_u64(OUTPUT_U64);
+            assert_eq!(INPUT_U64, decoded);
+        }
+
         #[cfg(feature = "nightly")]
         extern crate test;
 
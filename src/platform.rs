@@ -0,0 +1,193 @@
+//! Runtime CPU feature detection.
+//!
+//! Instead of requiring nightly and `RUSTFLAGS="-C target-cpu=native"` to
+//! ever reach the BMI2 fast path, we detect supported instruction set
+//! extensions once at runtime (following the same approach as BLAKE3's
+//! `platform.rs`) and cache the result in a static, so a single stable
+//! binary gets the fast path on capable CPUs and falls back to `portable`
+//! everywhere else.
+
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+const UNINIT: usize = usize::MAX;
+const PORTABLE: usize = 0;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const BMI2: usize = 1;
+
+static DETECTED: AtomicUsize = AtomicUsize::new(UNINIT);
+
+/// The instruction set extension used to implement morton encode/decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    Portable,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Bmi2,
+}
+
+impl Platform {
+    /// Detect the best available platform, caching the result after the
+    /// first call.
+    pub fn detect() -> Platform {
+        let cached = DETECTED.load(Relaxed);
+        if cached != UNINIT {
+            return Platform::from_usize(cached);
+        }
+        let detected = Platform::detect_uncached();
+        DETECTED.store(detected.to_usize(), Relaxed);
+        detected
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn detect_uncached() -> Platform {
+        if is_x86_feature_detected!("bmi2") {
+            return Platform::Bmi2;
+        }
+        Platform::Portable
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    fn detect_uncached() -> Platform {
+        Platform::Portable
+    }
+
+    fn to_usize(self) -> usize {
+        match self {
+            Platform::Portable => PORTABLE,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => BMI2,
+        }
+    }
+
+    fn from_usize(x: usize) -> Platform {
+        match x {
+            PORTABLE => Platform::Portable,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            BMI2 => Platform::Bmi2,
+            _ => unreachable!("corrupt cached platform value"),
+        }
+    }
+
+    pub fn morton_encode(&self, x: u32, y: u32) -> u64 {
+        match self {
+            Platform::Portable => crate::portable::morton_encode(x, y),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_encode(x, y) },
+        }
+    }
+
+    pub fn morton_decode(&self, a: u64) -> (u32, u32) {
+        match self {
+            Platform::Portable => crate::portable::morton_decode(a),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_decode(a) },
+        }
+    }
+
+    pub fn morton_encode_3d(&self, x: u32, y: u32, z: u32) -> u64 {
+        match self {
+            Platform::Portable => crate::portable::morton_encode_3d(x, y, z),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_encode_3d(x, y, z) },
+        }
+    }
+
+    pub fn morton_decode_3d(&self, a: u64) -> (u32, u32, u32) {
+        match self {
+            Platform::Portable => crate::portable::morton_decode_3d(a),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_decode_3d(a) },
+        }
+    }
+
+    pub fn morton_encode_u16(&self, x: u16, y: u16) -> u32 {
+        match self {
+            Platform::Portable => crate::portable::morton_encode_u16(x, y),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_encode_u16(x, y) },
+        }
+    }
+
+    pub fn morton_decode_u16(&self, a: u32) -> (u16, u16) {
+        match self {
+            Platform::Portable => crate::portable::morton_decode_u16(a),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_decode_u16(a) },
+        }
+    }
+
+    pub fn morton_encode_u64(&self, x: u64, y: u64) -> u128 {
+        match self {
+            Platform::Portable => crate::portable::morton_encode_u64(x, y),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_encode_u64(x, y) },
+        }
+    }
+
+    pub fn morton_decode_u64(&self, a: u128) -> (u64, u64) {
+        match self {
+            Platform::Portable => crate::portable::morton_decode_u64(a),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Platform::Bmi2 => unsafe { crate::bmi2::morton_decode_u64(a) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        INPUT, INPUT_3D, INPUT_U16, INPUT_U64, OUTPUT, OUTPUT_3D, OUTPUT_U16, OUTPUT_U64,
+    };
+
+    #[test]
+    fn test_portable_matches_detected() {
+        let (x, y) = INPUT;
+        assert_eq!(OUTPUT, Platform::Portable.morton_encode(x, y));
+        assert_eq!(INPUT, Platform::Portable.morton_decode(OUTPUT));
+
+        let (x3, y3, z3) = INPUT_3D;
+        assert_eq!(OUTPUT_3D, Platform::Portable.morton_encode_3d(x3, y3, z3));
+        assert_eq!(INPUT_3D, Platform::Portable.morton_decode_3d(OUTPUT_3D));
+
+        let (x16, y16) = INPUT_U16;
+        assert_eq!(OUTPUT_U16, Platform::Portable.morton_encode_u16(x16, y16));
+        assert_eq!(INPUT_U16, Platform::Portable.morton_decode_u16(OUTPUT_U16));
+
+        let (x64, y64) = INPUT_U64;
+        assert_eq!(OUTPUT_U64, Platform::Portable.morton_encode_u64(x64, y64));
+        assert_eq!(INPUT_U64, Platform::Portable.morton_decode_u64(OUTPUT_U64));
+
+        let detected = Platform::detect();
+        assert_eq!(OUTPUT, detected.morton_encode(x, y));
+        assert_eq!(INPUT, detected.morton_decode(OUTPUT));
+        assert_eq!(OUTPUT_3D, detected.morton_encode_3d(x3, y3, z3));
+        assert_eq!(INPUT_3D, detected.morton_decode_3d(OUTPUT_3D));
+        assert_eq!(OUTPUT_U16, detected.morton_encode_u16(x16, y16));
+        assert_eq!(INPUT_U16, detected.morton_decode_u16(OUTPUT_U16));
+        assert_eq!(OUTPUT_U64, detected.morton_encode_u64(x64, y64));
+        assert_eq!(INPUT_U64, detected.morton_decode_u64(OUTPUT_U64));
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn test_bmi2_matches_portable_when_available() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+        let (x, y) = INPUT;
+        assert_eq!(OUTPUT, Platform::Bmi2.morton_encode(x, y));
+        assert_eq!(INPUT, Platform::Bmi2.morton_decode(OUTPUT));
+
+        let (x, y, z) = INPUT_3D;
+        assert_eq!(OUTPUT_3D, Platform::Bmi2.morton_encode_3d(x, y, z));
+        assert_eq!(INPUT_3D, Platform::Bmi2.morton_decode_3d(OUTPUT_3D));
+
+        let (x16, y16) = INPUT_U16;
+        assert_eq!(OUTPUT_U16, Platform::Bmi2.morton_encode_u16(x16, y16));
+        assert_eq!(INPUT_U16, Platform::Bmi2.morton_decode_u16(OUTPUT_U16));
+
+        let (x64, y64) = INPUT_U64;
+        assert_eq!(OUTPUT_U64, Platform::Bmi2.morton_encode_u64(x64, y64));
+        assert_eq!(INPUT_U64, Platform::Bmi2.morton_decode_u64(OUTPUT_U64));
+    }
+}
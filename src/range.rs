@@ -0,0 +1,260 @@
+//! Z-order range query helpers.
+//!
+//! Morton codes are mostly used to drive spatial range queries: decode
+//! every candidate key, discard the ones outside the query box. The
+//! increment/decrement helpers work on the encoded key without decoding it.
+//! [`zorder_next_in_range`] decodes only the current key and the two box
+//! corners, once per call, so a Z-curve range scan can skip straight to the
+//! next key that's actually inside the box.
+
+/// Width in bits of a single coordinate interleaved into a `dims`-wide
+/// Morton code, matching the `2 => 32` / `3 => 21` split already used by
+/// [`zorder_next_in_range`]. For the 3D case `3 * 21 = 63`, so the top bit
+/// of the `u64` key is unused padding -- not part of any dimension.
+///
+/// `dims` must be 2 or 3, matching the crate's 2D/3D encode/decode support.
+fn coord_bits(dims: u32) -> u32 {
+    match dims {
+        2 => 32,
+        3 => 21,
+        _ => panic!("unsupported dims {dims}: only 2 or 3 are supported"),
+    }
+}
+
+/// Interleave mask selecting every `dims`-th bit starting at `dim`, i.e.
+/// the bits belonging to dimension `dim` of a `dims`-dimensional Morton
+/// code.
+fn dim_mask(dim: u32, dims: u32) -> u64 {
+    let mut mask = 0u64;
+    let mut bit = dim;
+    for _ in 0..coord_bits(dims) {
+        mask |= 1 << bit;
+        bit += dims;
+    }
+    mask
+}
+
+/// Adds 1 to the coordinate packed into dimension `dim` of a `dims`-wide
+/// interleaved Morton code, without decoding.
+///
+/// Temporarily sets every bit outside `dim`'s mask so the carry from the
+/// `+ 1` can't spill into another dimension's bits, then restores them.
+///
+/// # Panics
+///
+/// Panics if `dims` is not 2 or 3, matching the crate's 2D/3D encode/decode
+/// support.
+pub fn morton_increment_dim(code: u64, dim: u32, dims: u32) -> u64 {
+    let mask = dim_mask(dim, dims);
+    let other = code & !mask;
+    (((code | !mask).wrapping_add(1)) & mask) | other
+}
+
+/// Subtracts 1 from the coordinate packed into dimension `dim` of a
+/// `dims`-wide interleaved Morton code, without decoding. Mirrors
+/// [`morton_increment_dim`]: clears every bit outside `dim`'s mask so the
+/// borrow from the `- 1` passes through them, masks the result back to
+/// `dim`'s bits, then restores the other bits.
+///
+/// # Panics
+///
+/// Panics if `dims` is not 2 or 3, matching the crate's 2D/3D encode/decode
+/// support.
+pub fn morton_decrement_dim(code: u64, dim: u32, dims: u32) -> u64 {
+    let mask = dim_mask(dim, dims);
+    let other = code & !mask;
+    (((code & mask).wrapping_sub(1)) & mask) | other
+}
+
+/// Smallest value in `[lo, hi]` (inclusive) whose low `free_bits` bits are
+/// free to choose and whose remaining high bits are fixed to `fixed`, or
+/// `None` if no such value exists.
+fn min_completion(fixed: u32, free_bits: u32, lo: u32, hi: u32) -> Option<u32> {
+    let span: u32 = if free_bits >= 32 {
+        u32::MAX
+    } else {
+        (1u32 << free_bits) - 1
+    };
+    let range_lo = fixed;
+    let range_hi = fixed | span;
+    if range_hi < lo || range_lo > hi {
+        return None;
+    }
+    Some(range_lo.max(lo))
+}
+
+/// Smallest per-dimension point `>= z` whose coordinates lie in
+/// `[lo, hi]` componentwise, or `None` if no such point exists within the
+/// `bits`-wide coordinate space.
+///
+/// This is the classic BIGMIN computation: find the lowest bit position at
+/// which `z` can still be pushed above itself (flipping a 0 bit to 1 while
+/// keeping every higher bit equal to `z`), then fill everything below that
+/// bit with the smallest completion still inside the box, checked
+/// independently per dimension.
+fn bigmin(z: &[u32], lo: &[u32], hi: &[u32], bits: u32) -> Option<Vec<u32>> {
+    let dims = z.len() as u32;
+
+    if (0..z.len()).all(|d| lo[d] <= z[d] && z[d] <= hi[d]) {
+        return Some(z.to_vec());
+    }
+
+    let total_bits = dims * bits;
+    'levels: for level in 0..total_bits {
+        let d = (level % dims) as usize;
+        let local_bit = level / dims;
+        if local_bit >= bits || (z[d] >> local_bit) & 1 != 0 {
+            continue;
+        }
+
+        let mut result = vec![0u32; dims as usize];
+        for dd in 0..dims as usize {
+            let (fixed, free_bits) = if dd == d {
+                let fixed = (z[d] | (1 << local_bit)) & !((1 << local_bit) - 1);
+                (fixed, local_bit)
+            } else {
+                let free_bits = if level > dd as u32 {
+                    (level - dd as u32).div_ceil(dims)
+                } else {
+                    0
+                };
+                let fixed = if free_bits >= 32 {
+                    0
+                } else {
+                    z[dd] & !((1u32 << free_bits) - 1)
+                };
+                (fixed, free_bits)
+            };
+            match min_completion(fixed, free_bits, lo[dd], hi[dd]) {
+                Some(v) => result[dd] = v,
+                None => continue 'levels,
+            }
+        }
+        return Some(result);
+    }
+    None
+}
+
+/// Smallest Morton code `>= code` that lies inside the axis-aligned box
+/// whose corners are `min` and `max` (both encoded the same way as
+/// `code`), or `None` if no such code exists. `dims` must be 2 or 3,
+/// matching the crate's 2D/3D encode/decode support.
+///
+/// This lets a Z-order range scan jump straight past runs of keys that
+/// fall outside the query box instead of decoding and rejecting them one
+/// at a time.
+pub fn zorder_next_in_range(code: u64, min: u64, max: u64, dims: u32) -> Option<u64> {
+    match dims {
+        2 => {
+            let z = crate::portable::morton_decode(code);
+            let lo = crate::portable::morton_decode(min);
+            let hi = crate::portable::morton_decode(max);
+            let next = bigmin(&[z.0, z.1], &[lo.0, lo.1], &[hi.0, hi.1], 32)?;
+            Some(crate::portable::morton_encode(next[0], next[1]))
+        }
+        3 => {
+            let z = crate::portable::morton_decode_3d(code);
+            let lo = crate::portable::morton_decode_3d(min);
+            let hi = crate::portable::morton_decode_3d(max);
+            let next = bigmin(
+                &[z.0, z.1, z.2],
+                &[lo.0, lo.1, lo.2],
+                &[hi.0, hi.1, hi.2],
+                21,
+            )?;
+            Some(crate::portable::morton_encode_3d(next[0], next[1], next[2]))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn test_increment_dim_rejects_unsupported_dims() {
+        morton_increment_dim(0, 0, 0);
+    }
+
+    #[test]
+    fn test_increment_decrement_x_roundtrip() {
+        let code = crate::morton_encode(0x1234, 0x5678);
+        let incremented = morton_increment_dim(code, 0, 2);
+        assert_eq!(crate::morton_decode(incremented), (0x1235, 0x5678));
+
+        let decremented = morton_decrement_dim(incremented, 0, 2);
+        assert_eq!(decremented, code);
+    }
+
+    #[test]
+    fn test_increment_decrement_y() {
+        let code = crate::morton_encode(0x1234, 0x5678);
+        let incremented = morton_increment_dim(code, 1, 2);
+        assert_eq!(crate::morton_decode(incremented), (0x1234, 0x5679));
+
+        let decremented = morton_decrement_dim(incremented, 1, 2);
+        assert_eq!(decremented, code);
+    }
+
+    #[test]
+    fn test_increment_decrement_x_roundtrip_3d() {
+        let code = crate::morton_encode_3d(0x12345, 0x6789a, 0x3456);
+        let incremented = morton_increment_dim(code, 0, 3);
+        assert_eq!(
+            crate::morton_decode_3d(incremented),
+            (0x12346, 0x6789a, 0x3456)
+        );
+
+        let decremented = morton_decrement_dim(incremented, 0, 3);
+        assert_eq!(decremented, code);
+    }
+
+    #[test]
+    fn test_increment_dim_3d_overflow_does_not_corrupt_top_bit() {
+        // x is 21 bits wide, so incrementing its max value must wrap to 0
+        // in the raw code -- not leak a carry into the unused bit 63.
+        let code = crate::morton_encode_3d(0x1fffff, 0, 0);
+        assert_eq!(morton_increment_dim(code, 0, 3), 0);
+    }
+
+    #[test]
+    fn test_zorder_next_in_range_already_inside() {
+        let min = crate::morton_encode(2, 2);
+        let max = crate::morton_encode(8, 8);
+        let code = crate::morton_encode(3, 4);
+        assert_eq!(Some(code), zorder_next_in_range(code, min, max, 2));
+    }
+
+    #[test]
+    fn test_zorder_next_in_range_skips_forward() {
+        // A box with a gap just above x=0,y=2: the next in-range code
+        // should be found without decoding every skipped key.
+        let min = crate::morton_encode(0, 2);
+        let max = crate::morton_encode(0, 4);
+        let z = crate::morton_encode(1, 0);
+        let next = zorder_next_in_range(z, min, max, 2).unwrap();
+        assert!(next >= z);
+        let (x, y) = crate::morton_decode(next);
+        assert!((0..=0).contains(&x) && (2..=4).contains(&y));
+    }
+
+    #[test]
+    fn test_zorder_next_in_range_none_past_box() {
+        let min = crate::morton_encode(0, 0);
+        let max = crate::morton_encode(1, 1);
+        let z = crate::morton_encode(5, 5);
+        assert_eq!(None, zorder_next_in_range(z, min, max, 2));
+    }
+
+    #[test]
+    fn test_zorder_next_in_range_3d() {
+        let min = crate::morton_encode_3d(2, 2, 2);
+        let max = crate::morton_encode_3d(8, 8, 8);
+        let code = crate::morton_encode_3d(9, 0, 0);
+        let next = zorder_next_in_range(code, min, max, 3).unwrap();
+        let (x, y, z) = crate::morton_decode_3d(next);
+        assert!((2..=8).contains(&x) && (2..=8).contains(&y) && (2..=8).contains(&z));
+    }
+}